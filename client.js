@@ -36,7 +36,7 @@ async function main() {
   console.log('user public key: ' + provider.wallet.publicKey);
 
   // Execute the RPC.
-  let tx = await program.rpc.execute({
+  let tx = await program.rpc.execute(false, {
     accounts: {
       decimal: priceFeedAccount.publicKey,
       user: provider.wallet.publicKey,
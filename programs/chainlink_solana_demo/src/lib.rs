@@ -1,55 +1,600 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{invoke, invoke_signed, set_return_data};
+use anchor_lang::solana_program::system_instruction;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 
+pub mod math;
+
+#[cfg(not(feature = "mock-chainlink"))]
 use chainlink_solana as chainlink;
 
+#[cfg(feature = "mock-chainlink")]
+pub mod mock_chainlink;
+#[cfg(feature = "mock-chainlink")]
+use mock_chainlink as chainlink;
+
+// The mock trusts any account passed as a feed, so it must never reach a public cluster
+#[cfg(all(
+    feature = "mock-chainlink",
+    any(feature = "devnet", feature = "testnet", feature = "mainnet")
+))]
+compile_error!("The mock-chainlink feature cannot be combined with devnet, testnet or mainnet");
+
 declare_id!("JC16qi56dgcLoaTVe4BvnCoDL6FhH5NtahA7jmWZFdqm");;
 
+/// Largest number of decimals accepted from a feed. Real feeds use far fewer, so
+/// anything above this is treated as malformed feed data.
+pub const MAX_FEED_DECIMALS: u8 = 30;
+
+/// Longest feed description, in bytes, that a `PriceCache` can hold.
+pub const MAX_DESCRIPTION_LEN: usize = 64;
+
+/// Seed prefix of the price cache PDAs created by `init_feeds`, followed by the creator
+/// and feed keys.
+pub const PRICE_CACHE_SEED: &[u8] = b"price_cache";
+
+/// Most feeds `init_feeds` and `check_feeds_fresh` accept in one call, to stay within
+/// transaction limits.
+pub const MAX_FEEDS_PER_BATCH: usize = 8;
+
+/// Size of the discriminator Anchor prepends to every account.
+pub const ANCHOR_DISCRIMINATOR: usize = 8;
+
+fn check_feed_decimals(decimals: u8) -> ProgramResult {
+    if decimals > MAX_FEED_DECIMALS {
+        msg!("Feed reported {} decimals, max is {}", decimals, MAX_FEED_DECIMALS);
+        return Err(ErrorCode::UnreasonableFeedDecimals.into());
+    }
+    Ok(())
+}
+
+/// Checks that `chainlink_program` is the Chainlink program the feed queries are sent
+/// to. Sending them anywhere else fails with an opaque error (such as `0xbbf`), so
+/// this is checked once per instruction, before any query. The mock build does not
+/// invoke a program and skips the program id check.
+fn check_chainlink_program(chainlink_program: &AccountInfo) -> ProgramResult {
+    if *chainlink_program.key == System::id() {
+        msg!("The system program was passed as the Chainlink program, check the account order");
+        return Err(ErrorCode::ChainlinkProgramIsSystemProgram.into());
+    }
+    if cfg!(not(feature = "mock-chainlink")) && *chainlink_program.key != chainlink_solana::ID {
+        msg!(
+            "Expected the Chainlink program {}, got {}. Check the program id passed by the client",
+            chainlink_solana::ID,
+            chainlink_program.key
+        );
+        return Err(ErrorCode::ChainlinkProgramMismatch.into());
+    }
+    Ok(())
+}
+
+/// Logs the original error from a feed query and maps it to `ChainlinkFeedNotReadable`.
+/// A failure inside the Chainlink program itself aborts the transaction, so this
+/// covers the errors surfaced to the caller, such as undecodable return data.
+fn feed_not_readable(err: ProgramError) -> ProgramError {
+    msg!("Chainlink feed query failed: {}", err);
+    msg!("Check that the feed account is a Chainlink feed on this cluster");
+    ErrorCode::ChainlinkFeedNotReadable.into()
+}
+
+fn query_round<'info>(
+    chainlink_program: &AccountInfo<'info>,
+    chainlink_feed: &AccountInfo<'info>,
+) -> Result<chainlink::Round, ProgramError> {
+    chainlink::latest_round_data(chainlink_program.clone(), chainlink_feed.clone())
+        .map_err(feed_not_readable)
+}
+
+fn query_description<'info>(
+    chainlink_program: &AccountInfo<'info>,
+    chainlink_feed: &AccountInfo<'info>,
+) -> Result<String, ProgramError> {
+    chainlink::description(chainlink_program.clone(), chainlink_feed.clone())
+        .map_err(feed_not_readable)
+}
+
+fn query_decimals<'info>(
+    chainlink_program: &AccountInfo<'info>,
+    chainlink_feed: &AccountInfo<'info>,
+) -> Result<u8, ProgramError> {
+    chainlink::decimals(chainlink_program.clone(), chainlink_feed.clone())
+        .map_err(feed_not_readable)
+}
+
+/// Creates the PDA `account` with `space` bytes owned by `program_id`, funded by
+/// `payer`. Anyone can send lamports to a PDA before it is created, which makes
+/// `create_account` fail, so a funded account is topped up to `lamports` and then
+/// allocated and assigned instead.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    lamports: u64,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let current_lamports = account.lamports();
+    if current_lamports == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let top_up = lamports.saturating_sub(current_lamports);
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// A fixed-point number with `decimals` decimal places.
+///
+/// `#[account]` derives `AnchorSerialize`/`AnchorDeserialize`, so other programs can
+/// consume a `Decimal` through CPI return data or a shared account. The borsh layout
+/// is 20 bytes: `value` as a little-endian `i128` (16 bytes) followed by `decimals`
+/// as a little-endian `u32` (4 bytes). When stored as an account, the data is
+/// prefixed with Anchor's 8 byte account discriminator.
 #[account]
+#[derive(Debug)]
 pub struct Decimal {
     pub value: i128,
     pub decimals: u32,
 }
 
 impl Decimal {
+    /// Serialized size of a `Decimal`, excluding the account discriminator.
+    pub const LEN: usize = 16 + 4;
+
     pub fn new(value: i128, decimals: u32) -> Self {
         Decimal { value, decimals }
     }
+
+    pub fn zero(decimals: u32) -> Self {
+        Decimal::new(0, decimals)
+    }
+
+    /// Returns true when the value is zero, regardless of `decimals`.
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    /// Rescales `a` and `b` to the larger of their two decimal counts so they can
+    /// be compared or combined directly. Returns `None` if either value overflows.
+    pub fn normalize_pair(a: Decimal, b: Decimal) -> Option<(Decimal, Decimal)> {
+        let decimals = a.decimals.max(b.decimals);
+        Some((a.scale_up(decimals)?, b.scale_up(decimals)?))
+    }
+
+    fn scale_up(&self, decimals: u32) -> Option<Decimal> {
+        let factor = math::pow10(decimals - self.decimals)?;
+        Some(Decimal::new(self.value.checked_mul(factor)?, decimals))
+    }
+
+    /// Rescales to `decimals` decimal places, truncating any dropped digits toward
+    /// zero. Returns `None` if scaling up overflows.
+    pub fn rescale(&self, decimals: u32) -> Option<Decimal> {
+        if decimals >= self.decimals {
+            return self.scale_up(decimals);
+        }
+        let value = match math::pow10(self.decimals - decimals) {
+            Some(factor) => self.value / factor,
+            None => 0,
+        };
+        Some(Decimal::new(value, decimals))
+    }
+
+    /// Converts to a raw token amount for a mint with `mint_decimals` decimals,
+    /// truncating any fractional remainder. Returns `None` for negative values or
+    /// amounts that do not fit in a `u64`.
+    pub fn to_token_amount(&self, mint_decimals: u8) -> Option<u64> {
+        if self.value < 0 {
+            return None;
+        }
+        let amount = self.rescale(u32::from(mint_decimals))?;
+        u64::try_from(amount.value).ok()
+    }
+
+    /// Parses a decimal string with an optional `e`/`E` exponent, such as `105.52`,
+    /// `1.0552e2` or `1.23e-16`. The resulting `decimals` is the number of fractional
+    /// mantissa digits minus the exponent; when that is negative the value is scaled
+    /// up instead so `decimals` is clamped to zero. Returns `None` for malformed input,
+    /// values that overflow, or more than `math::MAX_POW10` decimals, which could not
+    /// be rescaled.
+    pub fn from_scientific(s: &str) -> Option<Decimal> {
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+            None => (s, 0),
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(mantissa) => (true, mantissa),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        if !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut value: i128 = format!("{}{}", integer, fraction).parse().ok()?;
+        if negative {
+            value = -value;
+        }
+        let decimals = i64::try_from(fraction.len()).ok()?.checked_sub(exponent)?;
+        if decimals > i64::from(math::MAX_POW10) {
+            return None;
+        }
+        if decimals >= 0 {
+            return Some(Decimal::new(value, u32::try_from(decimals).ok()?));
+        }
+        if value == 0 {
+            return Some(Decimal::zero(0));
+        }
+        let factor = math::pow10(u32::try_from(decimals.checked_neg()?).ok()?)?;
+        Some(Decimal::new(value.checked_mul(factor)?, 0))
+    }
+
+    /// Adds a raw token `amount` with `amount_decimals` decimals, returning the sum
+    /// at the larger of the two scales, or `None` on overflow.
+    pub fn add_token_amount(&self, amount: u64, amount_decimals: u8) -> Option<Decimal> {
+        let amount = Decimal::new(i128::from(amount), u32::from(amount_decimals));
+        self.checked_add(&amount)
+    }
+
+    /// Adds `rhs`, returning the sum at the larger of the two scales, or `None` on overflow.
+    pub fn checked_add(&self, rhs: &Decimal) -> Option<Decimal> {
+        let (a, b) = Decimal::normalize_pair(self.clone(), rhs.clone())?;
+        Some(Decimal::new(a.value.checked_add(b.value)?, a.decimals))
+    }
+
+    /// Subtracts `rhs`, returning the difference at the larger of the two scales, or
+    /// `None` on overflow.
+    pub fn checked_sub(&self, rhs: &Decimal) -> Option<Decimal> {
+        let (a, b) = Decimal::normalize_pair(self.clone(), rhs.clone())?;
+        Some(Decimal::new(a.value.checked_sub(b.value)?, a.decimals))
+    }
+
+    /// Bounds the value to `min..=max`, comparing across scales. The bound that is
+    /// hit is returned at its own scale. Returns `None` if `min` is greater than `max`.
+    pub fn checked_clamp(&self, min: &Decimal, max: &Decimal) -> Option<Decimal> {
+        if min > max {
+            return None;
+        }
+        if self < min {
+            Some(min.clone())
+        } else if self > max {
+            Some(max.clone())
+        } else {
+            Some(self.clone())
+        }
+    }
+
+    /// Like `checked_clamp`, but panics if `min` is greater than `max`, matching
+    /// `Ord::clamp`. Use `checked_clamp` for bounds that are not known to be ordered.
+    ///
+    /// Takes `self` by value so that `price.clamp(&min, &max)` resolves to this method
+    /// rather than `Ord::clamp`.
+    pub fn clamp(self, min: &Decimal, max: &Decimal) -> Decimal {
+        self.checked_clamp(min, max)
+            .expect("Decimal::clamp called with min greater than max")
+    }
+
+    /// Compares against the integer `rhs`, scaled to this value's precision.
+    pub fn cmp_int(&self, rhs: i128) -> Ordering {
+        match math::pow10(self.decimals).and_then(|factor| rhs.checked_mul(factor)) {
+            Some(scaled) => self.value.cmp(&scaled),
+            None if rhs == 0 => self.value.cmp(&0),
+            // The scaled integer is larger in magnitude than any i128, so the sign
+            // of `rhs` decides the ordering.
+            None if rhs > 0 => Ordering::Less,
+            None => Ordering::Greater,
+        }
+    }
+
+    /// Splits the value into its integer part and its zero-padded fractional digits,
+    /// e.g. `105.52000000` becomes `(105, "52000000")`.
+    ///
+    /// The sign is carried by the integer part and the fraction is always unsigned.
+    /// When the integer part is zero the sign is lost (`-0.005` becomes `(0, "005")`),
+    /// so check `value` directly if the sign matters.
+    pub fn split(&self) -> (i128, String) {
+        let (integer, fraction) = match math::pow10(self.decimals) {
+            Some(factor) => (self.value / factor, (self.value % factor).unsigned_abs()),
+            None => (0, self.value.unsigned_abs()),
+        };
+        if self.decimals == 0 {
+            return (integer, String::new());
+        }
+        (integer, format!("{:0>width$}", fraction, width = self.decimals as usize))
+    }
+
+    /// Formats like `Display`, but with at most `max_dp` fractional digits. Extra
+    /// digits are truncated, not rounded.
+    pub fn to_string_max_dp(&self, max_dp: u32) -> String {
+        let mut formatted = self.to_string();
+        if self.decimals > max_dp {
+            formatted.truncate(formatted.len() - (self.decimals - max_dp) as usize);
+            if max_dp == 0 {
+                // Drop the trailing decimal point
+                formatted.pop();
+            }
+        }
+        formatted
+    }
 }
 
 impl std::fmt::Display for Decimal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut scaled_val = self.value.to_string();
+        let mut scaled_val = self.value.unsigned_abs().to_string();
         if scaled_val.len() <= self.decimals as usize {
             scaled_val.insert_str(
                 0,
                 &vec!["0"; self.decimals as usize - scaled_val.len()].join(""),
             );
             scaled_val.insert_str(0, "0.");
-        } else {
+        } else if self.decimals > 0 {
             scaled_val.insert(scaled_val.len() - self.decimals as usize, '.');
         }
+        if self.value < 0 {
+            scaled_val.insert(0, '-');
+        }
         f.write_str(&scaled_val)
     }
 }
 
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match Decimal::normalize_pair(self.clone(), other.clone()) {
+            Some((a, b)) => a.value.cmp(&b.value),
+            // Only the side with fewer decimals can overflow when scaled up, and a
+            // non-zero value that overflows is larger in magnitude than the other side.
+            None => match (self.value.cmp(&0), other.value.cmp(&0)) {
+                (Ordering::Equal, sign) => sign.reverse(),
+                (sign, Ordering::Equal) => sign,
+                (sign, _) if self.decimals < other.decimals => sign,
+                (_, sign) => sign.reverse(),
+            },
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Two decimals are equal when they represent the same number, so `1.0` equals `1.00`.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+/// A `Decimal` kept inside another account, an instruction argument or return data.
+///
+/// `Decimal` is an `#[account]`, which the IDL lists as an account rather than a type,
+/// so clients cannot decode it when it is embedded elsewhere. This has the same
+/// 20 byte borsh layout and converts to and from `Decimal` for arithmetic.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct StoredDecimal {
+    pub value: i128,
+    pub decimals: u32,
+}
+
+impl StoredDecimal {
+    /// Serialized size of a `StoredDecimal`, the same as `Decimal::LEN`.
+    pub const LEN: usize = 16 + 4;
+}
+
+impl From<Decimal> for StoredDecimal {
+    fn from(decimal: Decimal) -> Self {
+        StoredDecimal {
+            value: decimal.value,
+            decimals: decimal.decimals,
+        }
+    }
+}
+
+impl From<StoredDecimal> for Decimal {
+    fn from(stored: StoredDecimal) -> Self {
+        Decimal::new(stored.value, stored.decimals)
+    }
+}
+
+/// The fields of the latest Chainlink `Round` kept by a `PriceCache`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct StoredRound {
+    pub round_id: u32,
+    pub answer: i128,
+    pub timestamp: u32,
+}
+
+impl StoredRound {
+    /// Serialized size of a `StoredRound`.
+    pub const LEN: usize = 4 + 16 + 4;
+}
+
+/// A reusable price account that anyone can refresh from the feed once
+/// `min_update_interval_seconds` have passed since `last_updated`.
+#[account]
+pub struct PriceCache {
+    /// Account allowed to manage the cache, set to the creator on initialization.
+    pub authority: Pubkey,
+    /// Feed the cache was created for. Refreshes from any other feed are rejected.
+    pub feed: Pubkey,
+    pub price: StoredDecimal,
+    pub last_updated: i64,
+    pub min_update_interval_seconds: i64,
+    /// Feed description, read on the first refresh and updated by `refresh_description`.
+    pub description: String,
+    /// Highest and lowest prices seen since initialization or the last `reset_high_low`.
+    pub high: Option<StoredDecimal>,
+    pub low: Option<StoredDecimal>,
+    /// Refreshed prices must fall within `[band_low, band_high]`. The band is
+    /// disabled while both bounds are zero.
+    pub band_low: StoredDecimal,
+    pub band_high: StoredDecimal,
+    /// Round the cached price was read from, so clients get the round details without
+    /// another query. Adds `StoredRound::LEN` (24) bytes to the account.
+    pub last_round: StoredRound,
+}
+
+impl PriceCache {
+    /// Serialized size of a `PriceCache`, excluding the account discriminator.
+    pub const LEN: usize = 32 // authority
+        + 32 // feed
+        + StoredDecimal::LEN // price
+        + 8 // last_updated
+        + 8 // min_update_interval_seconds
+        + 4 + MAX_DESCRIPTION_LEN // description
+        + 2 * (1 + StoredDecimal::LEN) // high, low
+        + 2 * StoredDecimal::LEN // band_low, band_high
+        + StoredRound::LEN; // last_round
+
+    pub fn new(authority: Pubkey, feed: Pubkey, min_update_interval_seconds: i64) -> Self {
+        PriceCache {
+            authority,
+            feed,
+            price: Decimal::zero(0).into(),
+            last_updated: 0,
+            min_update_interval_seconds,
+            description: String::new(),
+            high: None,
+            low: None,
+            band_low: Decimal::zero(0).into(),
+            band_high: Decimal::zero(0).into(),
+            last_round: StoredRound::default(),
+        }
+    }
+
+    fn set_description(&mut self, description: String) -> ProgramResult {
+        if description.len() > MAX_DESCRIPTION_LEN {
+            return Err(ErrorCode::DescriptionTooLong.into());
+        }
+        self.description = description;
+        Ok(())
+    }
+
+    /// Sets the band refreshed prices must fall within. The bounds must be ordered and
+    /// have no more decimals than a feed may report.
+    fn set_band(&mut self, band_low: StoredDecimal, band_high: StoredDecimal) -> ProgramResult {
+        let max_decimals = u32::from(MAX_FEED_DECIMALS);
+        if band_low.decimals > max_decimals || band_high.decimals > max_decimals {
+            msg!("Price band bounds have more than {} decimals", max_decimals);
+            return Err(ErrorCode::InvalidPriceBand.into());
+        }
+        if Decimal::from(band_low) > Decimal::from(band_high) {
+            return Err(ErrorCode::InvalidPriceBand.into());
+        }
+        self.band_low = band_low;
+        self.band_high = band_high;
+        Ok(())
+    }
+
+    fn check_band(&self, price: &Decimal) -> ProgramResult {
+        let band_low = Decimal::from(self.band_low);
+        let band_high = Decimal::from(self.band_high);
+        if band_low.is_zero() && band_high.is_zero() {
+            return Ok(());
+        }
+        if *price < band_low || *price > band_high {
+            msg!("Price {} is outside the band [{}, {}]", price, band_low, band_high);
+            return Err(ErrorCode::PriceOutsideBand.into());
+        }
+        Ok(())
+    }
+
+    fn track_high_low(&mut self, price: &Decimal) {
+        if self.high.map_or(true, |high| *price > Decimal::from(high)) {
+            self.high = Some(price.clone().into());
+        }
+        if self.low.map_or(true, |low| *price < Decimal::from(low)) {
+            self.low = Some(price.clone().into());
+        }
+    }
+}
+
+/// The spread between a reference price and another price.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct Spread {
+    /// Absolute difference between the two prices.
+    pub absolute: StoredDecimal,
+    /// `absolute` relative to the reference price, in basis points. `None` when the
+    /// reference price is zero.
+    pub bps: Option<u64>,
+}
+
+impl Spread {
+    /// Returns `None` if the prices cannot be rescaled or the spread overflows.
+    pub fn new(reference: &Decimal, other: &Decimal) -> Option<Spread> {
+        let diff = other.checked_sub(reference)?;
+        let absolute = Decimal::new(diff.value.checked_abs()?, diff.decimals);
+        let bps = if reference.is_zero() {
+            None
+        } else {
+            let reference = reference.rescale(absolute.decimals)?;
+            let bps = absolute.value.checked_mul(10_000)? / reference.value.checked_abs()?;
+            Some(u64::try_from(bps).ok()?)
+        };
+        Some(Spread {
+            absolute: absolute.into(),
+            bps,
+        })
+    }
+}
+
 #[program]
 pub mod chainlink_solana_demo {
     use super::*;
-        pub fn execute(ctx: Context<Execute>) -> ProgramResult  {
-        let round = chainlink::latest_round_data(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_feed.to_account_info(),
+    /// Reads the latest price into a new `Decimal` account. Negative answers are
+    /// rejected unless `allow_negative` is set, e.g. for spread or index feeds.
+    pub fn execute(ctx: Context<Execute>, allow_negative: bool) -> ProgramResult  {
+        check_chainlink_program(&ctx.accounts.chainlink_program)?;
+        let round = query_round(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
         )?;
 
-        let description = chainlink::description(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_feed.to_account_info(),
+        let description = query_description(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
         )?;
 
-        let decimals = chainlink::decimals(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_feed.to_account_info(),
+        let decimals = query_decimals(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
         )?;
+        check_feed_decimals(decimals)?;
+        if round.answer < 0 && !allow_negative {
+            msg!("Feed answered {}, negative answers are not allowed", round.answer);
+            return Err(ErrorCode::NegativeAnswerNotAllowed.into());
+        }
 
         // Set the account value
         let decimal: &mut Account<Decimal> = &mut ctx.accounts.decimal;
@@ -61,11 +606,231 @@ pub mod chainlink_solana_demo {
         msg!("{} price is {}", description, decimal_print);
         Ok(())
     }
+
+    /// Reads the latest price without writing to any account. The price is returned
+    /// as a borsh encoded `Decimal` through return data.
+    pub fn peek_price(ctx: Context<PeekPrice>) -> ProgramResult {
+        check_chainlink_program(&ctx.accounts.chainlink_program)?;
+        let round = query_round(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+
+        let description = query_description(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+
+        let decimals = query_decimals(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+        check_feed_decimals(decimals)?;
+
+        let price = Decimal::new(round.answer, u32::from(decimals));
+        msg!("{} price is {}", description, price);
+        set_return_data(&price.try_to_vec()?);
+        Ok(())
+    }
+
+    pub fn initialize_price_cache(
+        ctx: Context<InitializePriceCache>,
+        min_update_interval_seconds: i64,
+    ) -> ProgramResult {
+        *ctx.accounts.price_cache = PriceCache::new(
+            ctx.accounts.user.key(),
+            ctx.accounts.chainlink_feed.key(),
+            min_update_interval_seconds,
+        );
+        Ok(())
+    }
+
+    /// Creates a price cache PDA for each of `feeds`, seeded by `PRICE_CACHE_SEED`, the
+    /// user key and the feed key. The PDAs are passed in `remaining_accounts`, in the
+    /// same order. Seeding by the user means nobody else can create a user's price
+    /// cache first and take its address.
+    pub fn init_feeds<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitFeeds<'info>>,
+        feeds: Vec<Pubkey>,
+        min_update_interval_seconds: i64,
+    ) -> ProgramResult {
+        if feeds.is_empty()
+            || feeds.len() > MAX_FEEDS_PER_BATCH
+            || feeds.len() != ctx.remaining_accounts.len()
+        {
+            return Err(ErrorCode::InvalidFeedBatch.into());
+        }
+
+        let space = ANCHOR_DISCRIMINATOR + PriceCache::LEN;
+        let lamports = Rent::get()?.minimum_balance(space);
+        let user = ctx.accounts.user.key();
+        for (feed, account) in feeds.iter().zip(ctx.remaining_accounts.iter()) {
+            let (address, bump) = Pubkey::find_program_address(
+                &[PRICE_CACHE_SEED, user.as_ref(), feed.as_ref()],
+                ctx.program_id,
+            );
+            if address != *account.key {
+                msg!("Expected price cache {} for feed {}", address, feed);
+                return Err(ErrorCode::PriceCacheAddressMismatch.into());
+            }
+
+            create_pda_account(
+                &ctx.accounts.user.to_account_info(),
+                account,
+                &ctx.accounts.system_program.to_account_info(),
+                ctx.program_id,
+                lamports,
+                space,
+                &[PRICE_CACHE_SEED, user.as_ref(), feed.as_ref(), &[bump]],
+            )?;
+
+            let price_cache = PriceCache::new(user, *feed, min_update_interval_seconds);
+            let mut data = account.try_borrow_mut_data()?;
+            price_cache.try_serialize(&mut &mut data[..])?;
+
+            emit!(FeedInitialized {
+                price_cache: address,
+                feed: *feed,
+            });
+        }
+        Ok(())
+    }
+
+    /// Refreshes the cached price. When `use_cached_description` is set and a
+    /// description has already been cached, the description CPI is skipped.
+    pub fn refresh_price(ctx: Context<RefreshPrice>, use_cached_description: bool) -> ProgramResult {
+        check_chainlink_program(&ctx.accounts.chainlink_program)?;
+        let now = Clock::get()?.unix_timestamp;
+        let price_cache = &mut ctx.accounts.price_cache;
+        if now - price_cache.last_updated < price_cache.min_update_interval_seconds {
+            return Err(ErrorCode::UpdateTooSoon.into());
+        }
+
+        let round = query_round(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+
+        let decimals = query_decimals(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+        check_feed_decimals(decimals)?;
+
+        if !use_cached_description || price_cache.description.is_empty() {
+            let description = query_description(
+                &ctx.accounts.chainlink_program,
+                &ctx.accounts.chainlink_feed,
+            )?;
+            price_cache.set_description(description)?;
+        }
+
+        let price = Decimal::new(round.answer, u32::from(decimals));
+        price_cache.check_band(&price)?;
+        price_cache.track_high_low(&price);
+        price_cache.price = price.into();
+        price_cache.last_updated = now;
+        price_cache.last_round = StoredRound {
+            round_id: round.round_id,
+            answer: round.answer,
+            timestamp: round.timestamp,
+        };
+
+        msg!("{} price refreshed to {}", price_cache.description, Decimal::from(price_cache.price));
+        emit!(PriceRefreshed {
+            price_cache: price_cache.key(),
+            value: round.answer,
+            decimals: u32::from(decimals),
+            last_updated: now,
+        });
+        Ok(())
+    }
+
+    pub fn refresh_description(ctx: Context<RefreshPrice>) -> ProgramResult {
+        check_chainlink_program(&ctx.accounts.chainlink_program)?;
+        let description = query_description(
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.chainlink_feed,
+        )?;
+        msg!("Cached description set to {}", description);
+        ctx.accounts.price_cache.set_description(description)
+    }
+
+    /// Returns the cached high and low prices as a borsh encoded
+    /// `(Option<StoredDecimal>, Option<StoredDecimal>)` through return data.
+    pub fn get_high_low(ctx: Context<ReadPriceCache>) -> ProgramResult {
+        let price_cache = &ctx.accounts.price_cache;
+        set_return_data(&(price_cache.high, price_cache.low).try_to_vec()?);
+        Ok(())
+    }
+
+    /// Returns the round of the cached price as a borsh encoded `StoredRound` through
+    /// return data.
+    pub fn get_last_round(ctx: Context<ReadPriceCache>) -> ProgramResult {
+        set_return_data(&ctx.accounts.price_cache.last_round.try_to_vec()?);
+        Ok(())
+    }
+
+    pub fn reset_high_low(ctx: Context<UpdatePriceCache>) -> ProgramResult {
+        let price_cache = &mut ctx.accounts.price_cache;
+        price_cache.high = None;
+        price_cache.low = None;
+        Ok(())
+    }
+
+    /// Sets the band refreshed prices must fall within. Pass zero for both bounds to
+    /// disable the check.
+    pub fn set_price_band(
+        ctx: Context<UpdatePriceCache>,
+        band_low: StoredDecimal,
+        band_high: StoredDecimal,
+    ) -> ProgramResult {
+        ctx.accounts.price_cache.set_band(band_low, band_high)?;
+        msg!("Price band set to [{}, {}]", Decimal::from(band_low), Decimal::from(band_high));
+        Ok(())
+    }
+
+    /// Checks that every feed passed in `remaining_accounts` has a latest round no older
+    /// than `max_staleness_seconds`. The stale feed keys are returned as a borsh encoded
+    /// `Vec<Pubkey>` through return data, empty when all feeds are fresh. No account is
+    /// written.
+    pub fn check_feeds_fresh<'info>(
+        ctx: Context<'_, '_, '_, 'info, CheckFeedsFresh<'info>>,
+        max_staleness_seconds: i64,
+    ) -> ProgramResult {
+        if ctx.remaining_accounts.is_empty() || ctx.remaining_accounts.len() > MAX_FEEDS_PER_BATCH {
+            return Err(ErrorCode::InvalidFeedBatch.into());
+        }
+        check_chainlink_program(&ctx.accounts.chainlink_program)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut stale = Vec::new();
+        for feed in ctx.remaining_accounts.iter() {
+            let round = query_round(&ctx.accounts.chainlink_program, feed)?;
+            let age = now - i64::from(round.timestamp);
+            if age > max_staleness_seconds {
+                msg!("Feed {} is stale, last round is {} seconds old", feed.key, age);
+                stale.push(*feed.key);
+            }
+        }
+        set_return_data(&stale.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Computes the spread of `other` relative to `reference` and returns it as a
+    /// borsh encoded `Spread` through return data.
+    pub fn compute_spread(ctx: Context<ComputeSpread>) -> ProgramResult {
+        let spread = Spread::new(&ctx.accounts.reference, &ctx.accounts.other)
+            .ok_or(ErrorCode::MathOverflow)?;
+        msg!("Spread is {}", Decimal::from(spread.absolute));
+        set_return_data(&spread.try_to_vec()?);
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
 pub struct Execute<'info> {
-    #[account(init, payer = user, space = 100)]
+    #[account(init, payer = user, space = ANCHOR_DISCRIMINATOR + Decimal::LEN)]
     pub decimal: Account<'info, Decimal>,
     #[account(mut)]
     pub user: Signer<'info>,
@@ -73,6 +838,488 @@ pub struct Execute<'info> {
     pub chainlink_feed: AccountInfo<'info>,
     /// CHECK: This is the Chainlink program library on Devnet
     pub chainlink_program: AccountInfo<'info>,
-    /// CHECK: This is the devnet system program
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+pub struct PeekPrice<'info> {
+    /// CHECK: We're reading data from this specified chainlink feed
+    pub chainlink_feed: AccountInfo<'info>,
+    /// CHECK: This is the Chainlink program library on Devnet
+    pub chainlink_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePriceCache<'info> {
+    #[account(init, payer = user, space = ANCHOR_DISCRIMINATOR + PriceCache::LEN)]
+    pub price_cache: Account<'info, PriceCache>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: This is the chainlink feed the cache will be refreshed from
+    pub chainlink_feed: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitFeeds<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshPrice<'info> {
+    #[account(mut, constraint = price_cache.feed == chainlink_feed.key() @ ErrorCode::FeedAccountMismatch)]
+    pub price_cache: Account<'info, PriceCache>,
+    /// CHECK: We're reading data from this specified chainlink feed
+    pub chainlink_feed: AccountInfo<'info>,
+    /// CHECK: This is the Chainlink program library on Devnet
+    pub chainlink_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadPriceCache<'info> {
+    pub price_cache: Account<'info, PriceCache>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePriceCache<'info> {
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub price_cache: Account<'info, PriceCache>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CheckFeedsFresh<'info> {
+    /// CHECK: This is the Chainlink program library on Devnet
+    pub chainlink_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeSpread<'info> {
+    pub reference: Account<'info, Decimal>,
+    pub other: Account<'info, Decimal>,
+}
+
+#[event]
+pub struct PriceRefreshed {
+    pub price_cache: Pubkey,
+    pub value: i128,
+    pub decimals: u32,
+    pub last_updated: i64,
+}
+
+#[event]
+pub struct FeedInitialized {
+    pub price_cache: Pubkey,
+    pub feed: Pubkey,
+}
+
+#[error]
+pub enum ErrorCode {
+    #[msg("The feed reported an unreasonable number of decimals")]
+    UnreasonableFeedDecimals,
+    #[msg("The minimum update interval has not elapsed since the last refresh")]
+    UpdateTooSoon,
+    #[msg("The feed description is too long to cache")]
+    DescriptionTooLong,
+    #[msg("The feed answered with a negative value")]
+    NegativeAnswerNotAllowed,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("The Chainlink feed could not be read")]
+    ChainlinkFeedNotReadable,
+    #[msg("The Chainlink program account does not match the Chainlink program")]
+    ChainlinkProgramMismatch,
+    #[msg("The signer is not the price cache authority")]
+    Unauthorized,
+    #[msg("The system program was passed as the Chainlink program")]
+    ChainlinkProgramIsSystemProgram,
+    #[msg("The feed does not match the feed the price cache was created for")]
+    FeedAccountMismatch,
+    #[msg("The price is outside the configured price band")]
+    PriceOutsideBand,
+    #[msg("The price band bounds are out of order or have too many decimals")]
+    InvalidPriceBand,
+    #[msg("A feed batch must contain between 1 and 8 feeds, each with its price cache account")]
+    InvalidFeedBatch,
+    #[msg("The price cache account is not the PDA for the user and its feed")]
+    PriceCacheAddressMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_pair_scales_to_larger_decimals() {
+        let (a, b) = Decimal::normalize_pair(Decimal::new(15, 1), Decimal::new(2, 3)).unwrap();
+        assert_eq!((a.value, a.decimals), (1500, 3));
+        assert_eq!((b.value, b.decimals), (2, 3));
+
+        // The order of the arguments is kept
+        let (a, b) = Decimal::normalize_pair(Decimal::new(2, 3), Decimal::new(15, 1)).unwrap();
+        assert_eq!((a.value, a.decimals), (2, 3));
+        assert_eq!((b.value, b.decimals), (1500, 3));
+
+        // Negative values are scaled the same way
+        let (a, _) = Decimal::normalize_pair(Decimal::new(-15, 1), Decimal::new(2, 3)).unwrap();
+        assert_eq!((a.value, a.decimals), (-1500, 3));
+    }
+
+    #[test]
+    fn normalize_pair_overflow() {
+        assert!(Decimal::normalize_pair(Decimal::new(i128::MAX, 0), Decimal::new(1, 1)).is_none());
+        assert!(Decimal::normalize_pair(Decimal::new(1, 1), Decimal::new(i128::MIN, 0)).is_none());
+        // Scaling factors past 10^38 do not fit in an i128
+        assert!(Decimal::normalize_pair(Decimal::new(1, 0), Decimal::new(1, 39)).is_none());
+    }
+
+    #[test]
+    fn decimal_borsh_round_trip() {
+        for decimal in [
+            Decimal::new(10552000000, 8),
+            Decimal::new(-5, 2),
+            Decimal::new(0, 0),
+            Decimal::new(i128::MAX, 38),
+            Decimal::new(i128::MIN, u32::MAX),
+        ] {
+            let bytes = decimal.try_to_vec().unwrap();
+            assert_eq!(bytes.len(), 20);
+            let decoded = Decimal::try_from_slice(&bytes).unwrap();
+            assert_eq!((decoded.value, decoded.decimals), (decimal.value, decimal.decimals));
+        }
+    }
+
+    #[test]
+    fn decimal_borsh_layout() {
+        let bytes = Decimal::new(-2, 7).try_to_vec().unwrap();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(&bytes[..16], &(-2i128).to_le_bytes());
+        assert_eq!(&bytes[16..], &7u32.to_le_bytes());
+    }
+
+    #[test]
+    fn stored_decimal_matches_decimal_layout() {
+        let decimal = Decimal::new(-10552000000, 8);
+        let stored = StoredDecimal::from(decimal.clone());
+        let bytes = stored.try_to_vec().unwrap();
+        assert_eq!(bytes, decimal.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), StoredDecimal::LEN);
+        assert_eq!(Decimal::from(stored), decimal);
+    }
+
+    #[test]
+    fn feed_decimals_boundary() {
+        assert_eq!(check_feed_decimals(0), Ok(()));
+        assert_eq!(check_feed_decimals(MAX_FEED_DECIMALS), Ok(()));
+        assert_eq!(
+            check_feed_decimals(MAX_FEED_DECIMALS + 1),
+            Err(ErrorCode::UnreasonableFeedDecimals.into())
+        );
+        assert_eq!(
+            check_feed_decimals(u8::MAX),
+            Err(ErrorCode::UnreasonableFeedDecimals.into())
+        );
+    }
+
+    #[test]
+    fn split_into_integer_and_fraction() {
+        assert_eq!(Decimal::new(10552000000, 8).split(), (105, "52000000".to_string()));
+        assert_eq!(Decimal::new(-5, 3).split(), (0, "005".to_string()));
+        assert_eq!(Decimal::new(-10552, 2).split(), (-105, "52".to_string()));
+        assert_eq!(Decimal::new(42, 0).split(), (42, String::new()));
+    }
+
+    #[test]
+    fn zero_at_any_scale() {
+        assert!(Decimal::new(0, 8).is_zero());
+        assert!(Decimal::zero(18).is_zero());
+        assert_eq!(Decimal::zero(18).decimals, 18);
+        assert!(!Decimal::new(1, 8).is_zero());
+        assert!(!Decimal::new(-1, 0).is_zero());
+    }
+
+    #[test]
+    fn account_sizes_fit() {
+        assert_eq!(Decimal::new(i128::MIN, u32::MAX).try_to_vec().unwrap().len(), Decimal::LEN);
+
+        let mut price_cache = PriceCache::new(Pubkey::new_unique(), Pubkey::new_unique(), 60);
+        price_cache.set_description("x".repeat(MAX_DESCRIPTION_LEN)).unwrap();
+        price_cache.high = Some(Decimal::new(2, 8).into());
+        price_cache.low = Some(Decimal::new(1, 8).into());
+        price_cache.band_low = Decimal::new(1, 8).into();
+        price_cache.band_high = Decimal::new(2, 8).into();
+        assert_eq!(price_cache.try_to_vec().unwrap().len(), PriceCache::LEN);
+    }
+
+    #[test]
+    fn to_token_amount_rescales_to_mint_decimals() {
+        assert_eq!(Decimal::new(15, 1).to_token_amount(6), Some(1_500_000));
+        // Digits beyond the mint precision are truncated
+        assert_eq!(Decimal::new(1_234_567, 6).to_token_amount(2), Some(123));
+        assert_eq!(Decimal::zero(8).to_token_amount(0), Some(0));
+        // Negative values are not token amounts
+        assert_eq!(Decimal::new(-15, 1).to_token_amount(6), None);
+        // Amounts past u64::MAX, and scaling past i128, do not fit
+        assert_eq!(Decimal::new(i128::from(u64::MAX) + 1, 0).to_token_amount(0), None);
+        assert_eq!(Decimal::new(i128::from(u64::MAX), 0).to_token_amount(1), None);
+        assert_eq!(Decimal::new(1, 0).to_token_amount(u8::MAX), None);
+    }
+
+    #[test]
+    fn cmp_int_at_value_precision() {
+        let price = Decimal::new(10552, 2);
+        assert_eq!(price.cmp_int(105), Ordering::Greater);
+        assert_eq!(price.cmp_int(106), Ordering::Less);
+        assert_eq!(Decimal::new(10500, 2).cmp_int(105), Ordering::Equal);
+        assert_eq!(Decimal::new(-10552, 2).cmp_int(-105), Ordering::Less);
+    }
+
+    #[test]
+    fn cmp_int_past_i128_precision() {
+        // 10^39 does not fit in an i128, so any non-zero integer outweighs the value
+        let tiny = Decimal::new(i128::MAX, 39);
+        assert_eq!(tiny.cmp_int(1), Ordering::Less);
+        assert_eq!(tiny.cmp_int(-1), Ordering::Greater);
+        assert_eq!(tiny.cmp_int(0), Ordering::Greater);
+        assert_eq!(Decimal::new(-1, 39).cmp_int(0), Ordering::Less);
+        assert_eq!(Decimal::zero(39).cmp_int(0), Ordering::Equal);
+    }
+
+    #[test]
+    fn to_string_max_dp_truncates() {
+        assert_eq!(Decimal::new(1_000_000_000_000_000_000, 18).to_string_max_dp(4), "1.0000");
+        assert_eq!(Decimal::new(10559, 3).to_string_max_dp(2), "10.55");
+        assert_eq!(Decimal::new(-10559, 3).to_string_max_dp(2), "-10.55");
+        assert_eq!(Decimal::new(10559, 3).to_string_max_dp(0), "10");
+        // Values with no more than max_dp decimals are not changed
+        assert_eq!(Decimal::new(10552, 2).to_string_max_dp(2), "105.52");
+        assert_eq!(Decimal::new(10552, 2).to_string_max_dp(8), "105.52");
+        assert_eq!(Decimal::new(42, 0).to_string_max_dp(0), "42");
+    }
+
+    #[test]
+    fn add_token_amount_at_larger_scale() {
+        let sum = Decimal::new(25, 1).add_token_amount(1_500_000, 6).unwrap();
+        assert_eq!((sum.value, sum.decimals), (4_000_000, 6));
+        let sum = Decimal::new(25, 8).add_token_amount(15, 1).unwrap();
+        assert_eq!((sum.value, sum.decimals), (150_000_025, 8));
+        assert!(Decimal::new(i128::MAX, 0).add_token_amount(1, 0).is_none());
+    }
+
+    #[test]
+    fn display_negative_values() {
+        assert_eq!(Decimal::new(-5, 2).to_string(), "-0.05");
+        assert_eq!(Decimal::new(-10552, 2).to_string(), "-105.52");
+        assert_eq!(Decimal::new(-7, 0).to_string(), "-7");
+        assert_eq!(Decimal::new(i128::MIN, 0).to_string(), i128::MIN.to_string());
+        assert_eq!(Decimal::new(5, 2).to_string(), "0.05");
+    }
+
+    fn price_cache() -> PriceCache {
+        PriceCache::new(Pubkey::new_unique(), Pubkey::new_unique(), 0)
+    }
+
+    fn high_low(price_cache: &PriceCache) -> ((i128, u32), (i128, u32)) {
+        let high = price_cache.high.unwrap();
+        let low = price_cache.low.unwrap();
+        ((high.value, high.decimals), (low.value, low.decimals))
+    }
+
+    #[test]
+    fn track_high_low_sequences() {
+        let mut ascending = price_cache();
+        for value in [100, 101, 105] {
+            ascending.track_high_low(&Decimal::new(value, 2));
+        }
+        assert_eq!(high_low(&ascending), ((105, 2), (100, 2)));
+
+        let mut descending = price_cache();
+        for value in [105, 101, 100] {
+            descending.track_high_low(&Decimal::new(value, 2));
+        }
+        assert_eq!(high_low(&descending), ((105, 2), (100, 2)));
+
+        // A price equal to the current high or low does not replace it
+        descending.track_high_low(&Decimal::new(1050, 3));
+        assert_eq!(high_low(&descending), ((105, 2), (100, 2)));
+    }
+
+    #[test]
+    fn track_high_low_mixed_scales() {
+        let mut price_cache = price_cache();
+        price_cache.track_high_low(&Decimal::new(10552, 2));
+        price_cache.track_high_low(&Decimal::new(1_055_300_000, 7));
+        price_cache.track_high_low(&Decimal::new(105, 0));
+        assert_eq!(high_low(&price_cache), ((1_055_300_000, 7), (105, 0)));
+    }
+
+    fn parsed(s: &str) -> Option<(i128, u32)> {
+        Decimal::from_scientific(s).map(|decimal| (decimal.value, decimal.decimals))
+    }
+
+    #[test]
+    fn from_scientific_round_trip() {
+        assert_eq!(parsed("1.0552e2"), Some((10552, 2)));
+        assert_eq!(Decimal::from_scientific("1.0552e2").unwrap().to_string(), "105.52");
+        assert_eq!(parsed("1.23e-16"), Some((123, 18)));
+        assert_eq!(parsed("105.52"), Some((10552, 2)));
+        assert_eq!(parsed("-1.5E1"), Some((-15, 0)));
+        assert_eq!(parsed("+2"), Some((2, 0)));
+        assert_eq!(parsed(".5"), Some((5, 1)));
+        // A negative decimal count scales the value up instead
+        assert_eq!(parsed("1.5e3"), Some((1500, 0)));
+        assert_eq!(parsed("0e5"), Some((0, 0)));
+    }
+
+    #[test]
+    fn from_scientific_malformed() {
+        for s in ["", "e5", "1e", "1.2.3", "+-1", "-", ".", "1e2.5", "1,5", "abc", " 1"] {
+            assert_eq!(parsed(s), None, "{:?} should not parse", s);
+        }
+        // Values past i128 overflow
+        assert_eq!(parsed("1e39"), None);
+        assert_eq!(parsed("1e999999999999999999999"), None);
+        // More decimals than any i128 can be rescaled to
+        assert_eq!(parsed("1e-39"), None);
+        assert_eq!(parsed("0.1e-38"), None);
+        assert_eq!(parsed("1e-4000000000"), None);
+        assert_eq!(parsed("1e-38"), Some((1, 38)));
+    }
+
+    #[test]
+    fn check_band_bounds() {
+        let mut price_cache = price_cache();
+        price_cache.band_low = Decimal::new(100, 0).into();
+        price_cache.band_high = Decimal::new(11000, 2).into();
+
+        assert_eq!(price_cache.check_band(&Decimal::new(10552, 2)), Ok(()));
+        // The bounds are inclusive, whatever their scale
+        assert_eq!(price_cache.check_band(&Decimal::new(10000, 2)), Ok(()));
+        assert_eq!(price_cache.check_band(&Decimal::new(110, 0)), Ok(()));
+
+        let outside = Err(ErrorCode::PriceOutsideBand.into());
+        assert_eq!(price_cache.check_band(&Decimal::new(9999, 2)), outside);
+        assert_eq!(price_cache.check_band(&Decimal::new(1_100_001, 4)), outside);
+        assert_eq!(price_cache.check_band(&Decimal::new(-10552, 2)), outside);
+    }
+
+    #[test]
+    fn check_band_disabled_when_zero() {
+        let mut price_cache = price_cache();
+        assert_eq!(price_cache.check_band(&Decimal::new(-10552, 2)), Ok(()));
+        assert_eq!(price_cache.check_band(&Decimal::new(i128::MAX, 0)), Ok(()));
+
+        // Zero bounds at any scale disable the band
+        price_cache.band_low = Decimal::zero(8).into();
+        price_cache.band_high = Decimal::zero(2).into();
+        assert_eq!(price_cache.check_band(&Decimal::new(10552, 2)), Ok(()));
+
+        // A zero low bound with a non-zero high bound is enforced
+        price_cache.band_high = Decimal::new(1, 0).into();
+        assert_eq!(
+            price_cache.check_band(&Decimal::new(10552, 2)),
+            Err(ErrorCode::PriceOutsideBand.into())
+        );
+    }
+
+    #[test]
+    fn set_band_validates_bounds() {
+        let mut price_cache = price_cache();
+        let low = StoredDecimal::from(Decimal::new(100, 0));
+        let high = StoredDecimal::from(Decimal::new(11000, 2));
+        assert_eq!(price_cache.set_band(low, high), Ok(()));
+        assert_eq!(Decimal::from(price_cache.band_high), Decimal::new(110, 0));
+
+        let invalid = Err(ErrorCode::InvalidPriceBand.into());
+        assert_eq!(price_cache.set_band(high, low), invalid);
+
+        // Bounds may have as many decimals as a feed, but no more
+        let max_decimals = u32::from(MAX_FEED_DECIMALS);
+        let precise = StoredDecimal::from(Decimal::new(1, max_decimals));
+        assert_eq!(price_cache.set_band(precise, high), Ok(()));
+        let too_precise = StoredDecimal::from(Decimal::new(1, max_decimals + 1));
+        assert_eq!(price_cache.set_band(too_precise, high), invalid);
+        let unbounded = StoredDecimal::from(Decimal::new(1, u32::MAX));
+        assert_eq!(price_cache.set_band(low, unbounded), invalid);
+
+        // A rejected band leaves the previous band in place
+        assert_eq!(Decimal::from(price_cache.band_low), Decimal::new(1, max_decimals));
+    }
+
+    #[test]
+    fn clamp_across_scales() {
+        let min = Decimal::new(150, 2);
+        let max = Decimal::new(2500, 3);
+
+        // Method syntax with borrowed bounds resolves to Decimal::clamp, not Ord::clamp.
+        // The bound that is hit is returned at its own scale
+        let below = Decimal::new(1, 0).clamp(&min, &max);
+        assert_eq!((below.value, below.decimals), (150, 2));
+        let within = Decimal::new(20, 1).clamp(&min, &max);
+        assert_eq!((within.value, within.decimals), (20, 1));
+        let above = Decimal::new(3, 0).clamp(&min, &max);
+        assert_eq!((above.value, above.decimals), (2500, 3));
+
+        // Values equal to a bound are kept at their own scale
+        let at_min = Decimal::new(15, 1).clamp(&min, &max);
+        assert_eq!((at_min.value, at_min.decimals), (15, 1));
+    }
+
+    #[test]
+    fn checked_clamp_rejects_unordered_bounds() {
+        let min = Decimal::new(150, 2);
+        let max = Decimal::new(2500, 3);
+        let price = Decimal::new(20, 1);
+        assert_eq!(price.checked_clamp(&min, &max), Some(price.clone()));
+        assert_eq!(price.checked_clamp(&max, &min), None);
+        // Equal bounds at different scales are ordered
+        let clamped = price.checked_clamp(&Decimal::new(3, 0), &Decimal::new(300, 2)).unwrap();
+        assert_eq!((clamped.value, clamped.decimals), (3, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "min greater than max")]
+    fn clamp_panics_on_unordered_bounds() {
+        Decimal::new(20, 1).clamp(&Decimal::new(2500, 3), &Decimal::new(150, 2));
+    }
+
+    fn spread(reference: Decimal, other: Decimal) -> ((i128, u32), Option<u64>) {
+        let spread = Spread::new(&reference, &other).unwrap();
+        ((spread.absolute.value, spread.absolute.decimals), spread.bps)
+    }
+
+    #[test]
+    fn spread_above_and_below_reference() {
+        let reference = Decimal::new(10000, 2);
+        assert_eq!(spread(reference.clone(), Decimal::new(10150, 2)), ((150, 2), Some(150)));
+        // The spread is absolute, so a lower price gives the same result
+        assert_eq!(spread(reference.clone(), Decimal::new(9850, 2)), ((150, 2), Some(150)));
+        assert_eq!(spread(reference.clone(), reference), ((0, 2), Some(0)));
+        // A negative reference is compared by magnitude
+        assert_eq!(spread(Decimal::new(-100, 0), Decimal::new(-99, 0)), ((1, 0), Some(100)));
+    }
+
+    #[test]
+    fn spread_across_scales() {
+        // The absolute difference is at the larger scale
+        assert_eq!(spread(Decimal::new(10000, 2), Decimal::new(1015, 1)), ((150, 2), Some(150)));
+        assert_eq!(spread(Decimal::new(1015, 1), Decimal::new(10000, 2)), ((150, 2), Some(147)));
+    }
+
+    #[test]
+    fn spread_zero_reference() {
+        assert_eq!(spread(Decimal::zero(8), Decimal::new(5, 1)), ((50_000_000, 8), None));
+    }
+
+    #[test]
+    fn spread_overflow() {
+        // The difference does not fit in an i128
+        assert!(Spread::new(&Decimal::new(-1, 0), &Decimal::new(i128::MAX, 0)).is_none());
+        // The difference fits, but not in basis points
+        assert!(Spread::new(&Decimal::new(1, 0), &Decimal::new(i128::MAX / 2, 0)).is_none());
+        // The prices cannot be brought to a common scale
+        assert!(Spread::new(&Decimal::new(1, 0), &Decimal::new(1, 39)).is_none());
+    }
+}
@@ -0,0 +1,25 @@
+//! Checked numeric helpers shared by the `Decimal` arithmetic.
+
+/// Largest power of ten that fits in an `i128`, so the most decimals a value can be
+/// rescaled across.
+pub const MAX_POW10: u32 = 38;
+
+/// Returns `10^exp` as an `i128`, or `None` if the result does not fit
+/// (any `exp` above `MAX_POW10`).
+pub fn pow10(exp: u32) -> Option<i128> {
+    10i128.checked_pow(exp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pow10_range() {
+        assert_eq!(pow10(0), Some(1));
+        assert_eq!(pow10(8), Some(100_000_000));
+        assert_eq!(pow10(MAX_POW10), Some(100_000_000_000_000_000_000_000_000_000_000_000_000));
+        assert_eq!(pow10(MAX_POW10 + 1), None);
+        assert_eq!(pow10(u32::MAX), None);
+    }
+}
@@ -0,0 +1,52 @@
+//! Local stand-in for the Chainlink program, enabled with the `mock-chainlink` feature.
+//!
+//! Instead of invoking the Chainlink program through CPI, the functions here read
+//! the feed account directly. The account is expected to contain a borsh encoded
+//! [`MockFeed`], which makes it possible to run the demo under
+//! `solana-test-validator` with a fixture feed loaded via `--account`.
+//!
+//! The function signatures mirror `chainlink_solana` so the program code does not
+//! change between the two builds.
+use anchor_lang::prelude::*;
+
+pub use chainlink_solana::Round;
+
+/// Layout of a mock feed account: `decimals` (u8), `description` (u32 length
+/// prefix + UTF-8 bytes), followed by the `Round` fields `round_id` (u32),
+/// `slot` (u64), `timestamp` (u32) and `answer` (i128), all little-endian.
+/// Any trailing bytes in the account are ignored.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MockFeed {
+    pub decimals: u8,
+    pub description: String,
+    pub round: Round,
+}
+
+fn load(feed: &AccountInfo) -> Result<MockFeed, ProgramError> {
+    let data = feed.try_borrow_data()?;
+    MockFeed::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Returns the amount of decimal places stored in the mock feed.
+pub fn decimals<'info>(
+    _program_id: AccountInfo<'info>,
+    feed: AccountInfo<'info>,
+) -> Result<u8, ProgramError> {
+    Ok(load(&feed)?.decimals)
+}
+
+/// Returns the description stored in the mock feed.
+pub fn description<'info>(
+    _program_id: AccountInfo<'info>,
+    feed: AccountInfo<'info>,
+) -> Result<String, ProgramError> {
+    Ok(load(&feed)?.description)
+}
+
+/// Returns the round stored in the mock feed.
+pub fn latest_round_data<'info>(
+    _program_id: AccountInfo<'info>,
+    feed: AccountInfo<'info>,
+) -> Result<Round, ProgramError> {
+    Ok(load(&feed)?.round)
+}
@@ -2,6 +2,7 @@ import * as anchor from '@project-serum/anchor';
 import * as fs from 'fs';
 import { Program, BN } from '@project-serum/anchor';
 import { ChainlinkSolanaDemo } from '../target/types/chainlink_solana_demo';
+import { returnData } from './mock/return-data';
 const assert = require("assert");
 
 const CHAINLINK_PROGRAM_ID = "HEvSKofvBgfaexv23kMabbYqxasxU3mQ4ibBMEmJWHny";
@@ -25,7 +26,7 @@ describe('chainlink-solana-demo', () => {
     const priceFeedAccount = anchor.web3.Keypair.generate();
 
     // Execute the RPC.
-    let tx = await program.rpc.execute({
+    let tx = await program.rpc.execute(false, {
       accounts: {
         decimal: priceFeedAccount.publicKey,
         user: provider.wallet.publicKey,
@@ -45,4 +46,270 @@ describe('chainlink-solana-demo', () => {
     assert.ok(latestPrice.value / DIVISOR > 0);
 
   });
+
+  it('Peek at the SOL/USD price without an account!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+
+    const simulation = await program.simulate.peekPrice({
+      accounts: {
+        chainlinkFeed: CHAINLINK_FEED,
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+    });
+
+    // Decimal layout: value (i128), decimals (u32)
+    const price = returnData(simulation.raw, program.programId);
+    console.log('Peeked Price Is: ' + Number(price.readBigInt64LE(0)) / DIVISOR)
+    assert.ok(price.readBigInt64LE(0) > BigInt(0));
+    assert.equal(price.readUInt32LE(16), 8);
+  });
+
+  it('Reject the system program as the Chainlink program!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+    const priceFeedAccount = anchor.web3.Keypair.generate();
+
+    await assert.rejects(program.rpc.execute(false, {
+      accounts: {
+        decimal: priceFeedAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        chainlinkProgram: anchor.web3.SystemProgram.programId,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceFeedAccount],
+    }), /system program was passed as the Chainlink program/);
+  });
+
+  it('Reject a program other than the Chainlink program!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+    const priceFeedAccount = anchor.web3.Keypair.generate();
+
+    await assert.rejects(program.rpc.execute(false, {
+      accounts: {
+        decimal: priceFeedAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        chainlinkProgram: anchor.web3.Keypair.generate().publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceFeedAccount],
+    }), /does not match the Chainlink program/);
+  });
+
+  it('Skip the description query once it is cached!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+
+    //create an account to cache the price data, refreshable at any time
+    const priceCacheAccount = anchor.web3.Keypair.generate();
+    await program.rpc.initializePriceCache(new BN(0), {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceCacheAccount],
+    });
+
+    const accounts = {
+      priceCache: priceCacheAccount.publicKey,
+      chainlinkFeed: CHAINLINK_FEED,
+      chainlinkProgram: CHAINLINK_PROGRAM_ID
+    };
+    // Each feed query is a CPI into the Chainlink program
+    const feedQueries = (logs: string[]) =>
+      logs.filter((log) => log.startsWith('Program ' + CHAINLINK_PROGRAM_ID + ' invoke')).length;
+
+    // Nothing is cached yet, so the description is queried along with the round and decimals
+    const first = await program.simulate.refreshPrice(true, { accounts });
+    assert.equal(feedQueries(first.raw), 3);
+    await program.rpc.refreshPrice(true, { accounts, options: { commitment: "confirmed" } });
+
+    // Once cached, the description query is skipped
+    const cached = await program.simulate.refreshPrice(true, { accounts });
+    assert.equal(feedQueries(cached.raw), 2);
+
+    // Unless the caller asks for a fresh description
+    const forced = await program.simulate.refreshPrice(false, { accounts });
+    assert.equal(feedQueries(forced.raw), 3);
+  });
+
+  it('Refresh a cached price only after the update interval!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+
+    //create an account to cache the price data, refreshable at most once an hour
+    const priceCacheAccount = anchor.web3.Keypair.generate();
+    await program.rpc.initializePriceCache(new BN(3600), {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceCacheAccount],
+    });
+
+    const refresh = () => program.rpc.refreshPrice(true, {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+      options: { commitment: "confirmed" },
+    });
+
+    // The first refresh always goes through
+    await refresh();
+    const priceCache = await program.account.priceCache.fetch(priceCacheAccount.publicKey);
+    console.log('Cached Price Is: ' + priceCache.price.value / DIVISOR)
+    assert.ok(priceCache.price.value / DIVISOR > 0);
+    assert.ok(priceCache.lastUpdated.toNumber() > 0);
+    // The description is cached on the first refresh
+    assert.ok(priceCache.description.length > 0);
+    // The round the price was read from is kept alongside it
+    assert.ok(priceCache.lastRound.answer.eq(priceCache.price.value));
+    assert.ok(priceCache.lastRound.roundId > 0);
+
+    // The first price is both the high and the low
+    assert.ok(priceCache.high.value.eq(priceCache.price.value));
+    assert.ok(priceCache.low.value.eq(priceCache.price.value));
+
+    // A second refresh within the interval is rejected
+    await assert.rejects(refresh(), /minimum update interval has not elapsed/);
+
+    // The cache can only be refreshed from the feed it was created for
+    await assert.rejects(program.rpc.refreshPrice(true, {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        chainlinkFeed: anchor.web3.Keypair.generate().publicKey,
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+      options: { commitment: "confirmed" },
+    }), /does not match the feed the price cache was created for/);
+
+    // The description can be refreshed on demand
+    await program.rpc.refreshDescription({
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        chainlinkFeed: CHAINLINK_FEED,
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+      options: { commitment: "confirmed" },
+    });
+
+    const refreshed = await program.account.priceCache.fetch(priceCacheAccount.publicKey);
+    assert.equal(refreshed.description, priceCache.description);
+
+    // The authority can configure the band refreshed prices must fall within
+    await program.rpc.setPriceBand(
+      { value: new BN(1), decimals: 8 },
+      { value: new BN(2), decimals: 8 },
+      {
+        accounts: {
+          priceCache: priceCacheAccount.publicKey,
+          authority: provider.wallet.publicKey
+        },
+        options: { commitment: "confirmed" },
+      });
+    const banded = await program.account.priceCache.fetch(priceCacheAccount.publicKey);
+    assert.ok(banded.bandHigh.value.eq(new BN(2)));
+
+    // The authority can reset the high and low
+    await program.rpc.resetHighLow({
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        authority: provider.wallet.publicKey
+      },
+      options: { commitment: "confirmed" },
+    });
+    const reset = await program.account.priceCache.fetch(priceCacheAccount.publicKey);
+    assert.equal(reset.high, null);
+    assert.equal(reset.low, null);
+  });
+
+  it('Compute the spread between two prices!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+
+    //store the current price in two accounts
+    const priceAccounts = [anchor.web3.Keypair.generate(), anchor.web3.Keypair.generate()];
+    for (const priceAccount of priceAccounts) {
+      await program.rpc.execute(false, {
+        accounts: {
+          decimal: priceAccount.publicKey,
+          user: provider.wallet.publicKey,
+          chainlinkFeed: CHAINLINK_FEED,
+          chainlinkProgram: CHAINLINK_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId
+        },
+        options: { commitment: "confirmed" },
+        signers: [priceAccount],
+      });
+    }
+
+    // A price compared with itself has no spread
+    const simulation = await program.simulate.computeSpread({
+      accounts: {
+        reference: priceAccounts[0].publicKey,
+        other: priceAccounts[0].publicKey
+      },
+    });
+    // Spread layout: absolute value (i128), absolute decimals (u32), bps (Option<u64>)
+    const spread = returnData(simulation.raw, program.programId);
+    assert.equal(spread.readBigInt64LE(0), BigInt(0));
+    assert.equal(spread.readUInt8(20), 1);
+    assert.equal(spread.readBigUInt64LE(21), BigInt(0));
+
+    // Two reads of the same feed have a spread well under 100%
+    const otherSimulation = await program.simulate.computeSpread({
+      accounts: {
+        reference: priceAccounts[0].publicKey,
+        other: priceAccounts[1].publicKey
+      },
+    });
+    const otherSpread = returnData(otherSimulation.raw, program.programId);
+    assert.equal(otherSpread.readUInt8(20), 1);
+    assert.ok(otherSpread.readBigUInt64LE(21) < BigInt(10000));
+  });
+
+  it('Initialize price caches for several feeds at once!', async () => {
+    const program = anchor.workspace.ChainlinkSolanaDemo;
+
+    const feeds = [1, 2, 3].map(() => anchor.web3.Keypair.generate().publicKey);
+    const priceCaches = await Promise.all(feeds.map(async (feed) => {
+      const [address] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("price_cache"), provider.wallet.publicKey.toBuffer(), feed.toBuffer()],
+        program.programId
+      );
+      return address;
+    }));
+
+    // Sending lamports to a price cache address before it is created does not block it
+    await provider.send(new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.transfer({
+        fromPubkey: provider.wallet.publicKey,
+        toPubkey: priceCaches[0],
+        lamports: 1,
+      })
+    ), [], { commitment: "confirmed" });
+
+    await program.rpc.initFeeds(feeds, new BN(60), {
+      accounts: {
+        user: provider.wallet.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      remainingAccounts: priceCaches.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })),
+      options: { commitment: "confirmed" },
+    });
+
+    // Each price cache records the feed it was created for
+    for (let i = 0; i < feeds.length; i++) {
+      const priceCache = await program.account.priceCache.fetch(priceCaches[i]);
+      assert.ok(priceCache.feed.equals(feeds[i]));
+      assert.ok(priceCache.authority.equals(provider.wallet.publicKey));
+    }
+  });
 });
@@ -0,0 +1,156 @@
+import * as anchor from '@project-serum/anchor';
+import * as fs from 'fs';
+import { Program, BN } from '@project-serum/anchor';
+import { ChainlinkSolanaDemo } from '../../target/types/chainlink_solana_demo';
+import { mockFeedAddress } from './fixtures';
+import { returnData } from './return-data';
+const assert = require("assert");
+
+// Run with tests/mock/run.sh, which builds the program with the mock-chainlink
+// feature and loads the fixture feeds into a local validator
+const PROGRAM_ID = "JC16qi56dgcLoaTVe4BvnCoDL6FhH5NtahA7jmWZFdqm";
+// The mock does not invoke the Chainlink program, the real program id is passed
+// so the program account checks behave as on Devnet
+const CHAINLINK_PROGRAM_ID = "HEvSKofvBgfaexv23kMabbYqxasxU3mQ4ibBMEmJWHny";
+
+describe('chainlink-solana-demo with mock feeds', () => {
+  const provider = anchor.Provider.env();
+  anchor.setProvider(provider);
+
+  const idl = JSON.parse(fs.readFileSync('target/idl/chainlink_solana_demo.json', 'utf8'));
+  const program = new Program<ChainlinkSolanaDemo>(idl, PROGRAM_ID, provider);
+
+  it('Query the mock SOL/USD feed!', async () => {
+    const priceFeedAccount = anchor.web3.Keypair.generate();
+
+    await program.rpc.execute(false, {
+      accounts: {
+        decimal: priceFeedAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: mockFeedAddress("solUsd"),
+        chainlinkProgram: CHAINLINK_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceFeedAccount],
+    });
+
+    // The fixture answer is stored as is
+    const price = await program.account.decimal.fetch(priceFeedAccount.publicKey);
+    assert.ok(price.value.eq(new BN("10552000000")));
+    assert.equal(price.decimals, 8);
+  });
+
+  it('Peek at the mock SOL/USD price!', async () => {
+    const simulation = await program.simulate.peekPrice({
+      accounts: {
+        chainlinkFeed: mockFeedAddress("solUsd"),
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+    });
+
+    // Decimal layout: value (i128), decimals (u32)
+    const price = returnData(simulation.raw, program.programId);
+    assert.equal(price.length, 20);
+    assert.equal(price.readBigInt64LE(0), BigInt(10552000000));
+    assert.equal(price.readBigInt64LE(8), BigInt(0));
+    assert.equal(price.readUInt32LE(16), 8);
+  });
+
+  it('Read the last round of a refreshed price cache!', async () => {
+    const priceCacheAccount = anchor.web3.Keypair.generate();
+    await program.rpc.initializePriceCache(new BN(0), {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        user: provider.wallet.publicKey,
+        chainlinkFeed: mockFeedAddress("solUsd"),
+        systemProgram: anchor.web3.SystemProgram.programId
+      },
+      options: { commitment: "confirmed" },
+      signers: [priceCacheAccount],
+    });
+    await program.rpc.refreshPrice(true, {
+      accounts: {
+        priceCache: priceCacheAccount.publicKey,
+        chainlinkFeed: mockFeedAddress("solUsd"),
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+      options: { commitment: "confirmed" },
+    });
+
+    const simulation = await program.simulate.getLastRound({
+      accounts: { priceCache: priceCacheAccount.publicKey },
+    });
+
+    // StoredRound layout: round_id (u32), answer (i128), timestamp (u32)
+    const round = returnData(simulation.raw, program.programId);
+    assert.equal(round.length, 24);
+    assert.equal(round.readUInt32LE(0), 1);
+    assert.equal(round.readBigInt64LE(4), BigInt(10552000000));
+    assert.equal(round.readBigInt64LE(12), BigInt(0));
+    // The fixture round was written when the validator was started
+    const age = Math.floor(Date.now() / 1000) - round.readUInt32LE(20);
+    assert.ok(age >= 0 && age < 60 * 60);
+  });
+
+  it('Report which feeds are stale!', async () => {
+    const feeds = ["solUsd", "stale", "negative"].map((name) => ({
+      pubkey: mockFeedAddress(name),
+      isWritable: false,
+      isSigner: false,
+    }));
+    const checkFeedsFresh = (maxStalenessSeconds: number) => program.simulate.checkFeedsFresh(
+      new BN(maxStalenessSeconds),
+      {
+        accounts: { chainlinkProgram: CHAINLINK_PROGRAM_ID },
+        remainingAccounts: feeds,
+      }
+    );
+
+    // Only the feed updated two days ago is older than an hour
+    // Vec<Pubkey> layout: length (u32), followed by the keys
+    const stale = returnData((await checkFeedsFresh(60 * 60)).raw, program.programId);
+    assert.equal(stale.readUInt32LE(0), 1);
+    assert.ok(new anchor.web3.PublicKey(stale.slice(4, 36)).equals(mockFeedAddress("stale")));
+
+    // Within a week every feed is fresh
+    const fresh = returnData((await checkFeedsFresh(7 * 24 * 60 * 60)).raw, program.programId);
+    assert.equal(fresh.readUInt32LE(0), 0);
+  });
+
+  it('Reject a negative answer unless negative answers are allowed!', async () => {
+    const execute = (allowNegative: boolean, priceFeedAccount: anchor.web3.Keypair) =>
+      program.rpc.execute(allowNegative, {
+        accounts: {
+          decimal: priceFeedAccount.publicKey,
+          user: provider.wallet.publicKey,
+          chainlinkFeed: mockFeedAddress("negative"),
+          chainlinkProgram: CHAINLINK_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId
+        },
+        options: { commitment: "confirmed" },
+        signers: [priceFeedAccount],
+      });
+
+    await assert.rejects(
+      execute(false, anchor.web3.Keypair.generate()),
+      /The feed answered with a negative value/
+    );
+
+    const priceFeedAccount = anchor.web3.Keypair.generate();
+    await execute(true, priceFeedAccount);
+    const price = await program.account.decimal.fetch(priceFeedAccount.publicKey);
+    assert.ok(price.value.eq(new BN("-250000000")));
+    assert.equal(price.decimals, 8);
+  });
+
+  it('Report a feed that cannot be read!', async () => {
+    await assert.rejects(program.rpc.peekPrice({
+      accounts: {
+        chainlinkFeed: mockFeedAddress("undecodable"),
+        chainlinkProgram: CHAINLINK_PROGRAM_ID
+      },
+      options: { commitment: "confirmed" },
+    }), /The Chainlink feed could not be read/);
+  });
+});
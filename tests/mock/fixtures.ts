@@ -0,0 +1,103 @@
+import * as anchor from '@project-serum/anchor';
+import * as fs from 'fs';
+import * as path from 'path';
+
+// Mock feed accounts loaded into the local validator by tests/mock/run.sh. The
+// layout matches MockFeed in programs/chainlink_solana_demo/src/mock_chainlink.rs.
+export interface MockFeed {
+  decimals: number;
+  description: string;
+  roundId: number;
+  slot: number;
+  // Seconds before the fixtures are written, so fixtures stay fresh or stale
+  // relative to the time the tests run
+  age: number;
+  answer: bigint;
+}
+
+export const MOCK_FEEDS: { [name: string]: MockFeed } = {
+  // SOL / USD at 105.52, the same fixture as the README
+  solUsd: {
+    decimals: 8,
+    description: "SOL / USD",
+    roundId: 1,
+    slot: 1,
+    age: 0,
+    answer: BigInt(10552000000),
+  },
+  // A spread feed currently answering -2.5
+  negative: {
+    decimals: 8,
+    description: "SOL / USD spread",
+    roundId: 1,
+    slot: 1,
+    age: 0,
+    answer: BigInt(-250000000),
+  },
+  // ETH / USD whose latest round is two days old
+  stale: {
+    decimals: 8,
+    description: "ETH / USD",
+    roundId: 1,
+    slot: 1,
+    age: 2 * 24 * 60 * 60,
+    answer: BigInt(300000000000),
+  },
+};
+
+// Accounts passed as feeds that do not hold a valid MockFeed
+export const INVALID_FEEDS: { [name: string]: Buffer } = {
+  // Too short to decode, like a feed query whose return data cannot be read
+  undecodable: Buffer.from([0xff]),
+};
+
+function fixtureNames(): string[] {
+  return Object.keys(MOCK_FEEDS).concat(Object.keys(INVALID_FEEDS));
+}
+
+// Fixture addresses are derived from the fixture index so the tests and the
+// validator agree on them without sharing state
+export function mockFeedAddress(name: string): anchor.web3.PublicKey {
+  const index = fixtureNames().indexOf(name);
+  return anchor.web3.Keypair.fromSeed(new Uint8Array(32).fill(index + 1)).publicKey;
+}
+
+export function encodeMockFeed(feed: MockFeed, now: number): Buffer {
+  const description = Buffer.from(feed.description);
+  const data = Buffer.alloc(1 + 4 + description.length + 4 + 8 + 4 + 16);
+  let offset = 0;
+  offset = data.writeUInt8(feed.decimals, offset);
+  offset = data.writeUInt32LE(description.length, offset);
+  offset += description.copy(data, offset);
+  offset = data.writeUInt32LE(feed.roundId, offset);
+  offset = data.writeBigUInt64LE(BigInt(feed.slot), offset);
+  offset = data.writeUInt32LE(now - feed.age, offset);
+  offset = data.writeBigUInt64LE(BigInt.asUintN(64, feed.answer), offset); // answer, low 64 bits
+  data.writeBigInt64LE(BigInt.asIntN(64, feed.answer >> BigInt(64)), offset); // answer, high 64 bits
+  return data;
+}
+
+// Writes one account file per fixture to `dir` and prints the matching
+// solana-test-validator arguments
+if (require.main === module) {
+  const dir = process.argv[2];
+  const now = Math.floor(Date.now() / 1000);
+  fs.mkdirSync(dir, { recursive: true });
+  const args = fixtureNames().map((name) => {
+    const address = mockFeedAddress(name).toBase58();
+    const file = path.join(dir, name + ".json");
+    const data = name in MOCK_FEEDS ? encodeMockFeed(MOCK_FEEDS[name], now) : INVALID_FEEDS[name];
+    fs.writeFileSync(file, JSON.stringify({
+      pubkey: address,
+      account: {
+        lamports: 1000000000,
+        data: [data.toString("base64"), "base64"],
+        owner: anchor.web3.SystemProgram.programId.toBase58(),
+        executable: false,
+        rentEpoch: 0,
+      },
+    }));
+    return "--account " + address + " " + file;
+  });
+  console.log(args.join(" "));
+}
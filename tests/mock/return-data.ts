@@ -0,0 +1,8 @@
+import * as anchor from '@project-serum/anchor';
+
+// Decodes the return data set by the given program from simulated transaction logs
+export function returnData(logs: string[], programId: anchor.web3.PublicKey): Buffer {
+  const prefix = 'Program return: ' + programId.toBase58() + ' ';
+  const log = logs.find((log) => log.startsWith(prefix));
+  return Buffer.from(log.slice(prefix.length), 'base64');
+}
@@ -0,0 +1,26 @@
+#!/bin/sh
+# Runs the mock feed tests against a local validator, without access to Devnet.
+# The program is built with the mock-chainlink feature and deployed at genesis
+# together with the fixture feeds from tests/mock/fixtures.ts.
+set -e
+
+PROGRAM_ID=JC16qi56dgcLoaTVe4BvnCoDL6FhH5NtahA7jmWZFdqm
+FIXTURES=.anchor/mock-fixtures
+LEDGER=.anchor/mock-ledger
+
+anchor build -- --features mock-chainlink
+ACCOUNTS=$(yarn -s ts-node tests/mock/fixtures.ts "$FIXTURES")
+
+solana-test-validator --reset --quiet --ledger "$LEDGER" \
+  --mint "$(solana-keygen pubkey ./id.json)" \
+  --bpf-program "$PROGRAM_ID" target/deploy/chainlink_solana_demo.so \
+  $ACCOUNTS &
+VALIDATOR=$!
+trap 'kill $VALIDATOR' EXIT
+
+until solana cluster-version --url http://127.0.0.1:8899 >/dev/null 2>&1; do
+  sleep 1
+done
+
+ANCHOR_PROVIDER_URL=http://127.0.0.1:8899 ANCHOR_WALLET=./id.json \
+  yarn run ts-mocha -p ./tsconfig.json -t 1000000 tests/mock/chainlink-solana-demo-mock-test.ts